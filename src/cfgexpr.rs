@@ -0,0 +1,187 @@
+//! A small cfg-expression parser/evaluator (`all(...)`, `any(...)`,
+//! `not(...)`, bare identifiers, and `key = "value"` predicates), evaluated
+//! against a parsed `--target=` triple.
+
+use std::iter::Peekable;
+use std::str::Chars;
+
+#[derive(Debug, Clone)]
+pub enum CfgExpr {
+    All(Vec<CfgExpr>),
+    Any(Vec<CfgExpr>),
+    Not(Box<CfgExpr>),
+    /// A bare identifier (`key = None`) or a `key = "value"` predicate.
+    Predicate(String, Option<String>),
+}
+
+/// The components of a target triple relevant to cfg predicates, e.g.
+/// `arm-none-eabi` splits into arch `arm`, vendor `none`, os `eabi`.
+pub struct TargetCfg {
+    pub target_arch: String,
+    pub target_vendor: String,
+    pub target_os: String,
+    pub target_env: String,
+}
+
+impl TargetCfg {
+    pub fn from_triple(triple: &str) -> Self {
+        let parts: Vec<&str> = triple.split('-').collect();
+        TargetCfg {
+            target_arch: parts.first().copied().unwrap_or("").to_string(),
+            target_vendor: parts.get(1).copied().unwrap_or("").to_string(),
+            target_os: parts.get(2).copied().unwrap_or("").to_string(),
+            target_env: parts.get(3).copied().unwrap_or("").to_string(),
+        }
+    }
+
+    fn field(&self, key: &str) -> Option<&str> {
+        match key {
+            "target_arch" => Some(&self.target_arch),
+            "target_vendor" => Some(&self.target_vendor),
+            "target_os" => Some(&self.target_os),
+            "target_env" => Some(&self.target_env),
+            _ => None,
+        }
+    }
+}
+
+impl CfgExpr {
+    /// Parse a cfg-expression, e.g. `all(target_arch = "arm", target_os = "none")`.
+    pub fn parse(expr: &str) -> anyhow::Result<CfgExpr> {
+        let mut chars = expr.chars().peekable();
+        let result = parse_expr(&mut chars)?;
+        skip_ws(&mut chars);
+        if chars.peek().is_some() {
+            anyhow::bail!("unexpected trailing input in cfg expression: `{}`", expr);
+        }
+        Ok(result)
+    }
+
+    pub fn eval(&self, target: &TargetCfg) -> bool {
+        match self {
+            CfgExpr::All(exprs) => exprs.iter().all(|e| e.eval(target)),
+            CfgExpr::Any(exprs) => exprs.iter().any(|e| e.eval(target)),
+            CfgExpr::Not(expr) => !expr.eval(target),
+            CfgExpr::Predicate(key, Some(value)) => target.field(key) == Some(value.as_str()),
+            CfgExpr::Predicate(key, None) => [
+                &target.target_arch,
+                &target.target_vendor,
+                &target.target_os,
+                &target.target_env,
+            ]
+            .iter()
+            .any(|v| v.as_str() == key),
+        }
+    }
+}
+
+fn skip_ws(chars: &mut Peekable<Chars>) {
+    while matches!(chars.peek(), Some(c) if c.is_whitespace()) {
+        chars.next();
+    }
+}
+
+fn parse_ident(chars: &mut Peekable<Chars>) -> String {
+    let mut ident = String::new();
+    while matches!(chars.peek(), Some(c) if c.is_alphanumeric() || *c == '_') {
+        ident.push(chars.next().unwrap());
+    }
+    ident
+}
+
+fn parse_expr(chars: &mut Peekable<Chars>) -> anyhow::Result<CfgExpr> {
+    skip_ws(chars);
+    let ident = parse_ident(chars);
+    if ident.is_empty() {
+        anyhow::bail!("expected identifier in cfg expression");
+    }
+    skip_ws(chars);
+
+    match chars.peek() {
+        Some('(') => {
+            chars.next();
+            let mut items = Vec::new();
+            loop {
+                skip_ws(chars);
+                items.push(parse_expr(chars)?);
+                skip_ws(chars);
+                match chars.next() {
+                    Some(',') => continue,
+                    Some(')') => break,
+                    other => anyhow::bail!("expected ',' or ')' in cfg expression, found {:?}", other),
+                }
+            }
+            match ident.as_str() {
+                "all" => Ok(CfgExpr::All(items)),
+                "any" => Ok(CfgExpr::Any(items)),
+                "not" => {
+                    if items.len() != 1 {
+                        anyhow::bail!("not(...) takes exactly one argument");
+                    }
+                    Ok(CfgExpr::Not(Box::new(items.into_iter().next().unwrap())))
+                }
+                other => anyhow::bail!("unknown cfg function `{}`", other),
+            }
+        }
+        Some('=') => {
+            chars.next();
+            skip_ws(chars);
+            if chars.next() != Some('"') {
+                anyhow::bail!("expected a quoted string after `=` in cfg expression");
+            }
+            let mut value = String::new();
+            for c in chars.by_ref() {
+                if c == '"' {
+                    return Ok(CfgExpr::Predicate(ident, Some(value)));
+                }
+                value.push(c);
+            }
+            anyhow::bail!("unterminated string in cfg expression");
+        }
+        _ => Ok(CfgExpr::Predicate(ident, None)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn arm() -> TargetCfg {
+        TargetCfg::from_triple("arm-none-eabi")
+    }
+
+    #[test]
+    fn parses_and_evaluates_predicate() {
+        let expr = CfgExpr::parse(r#"target_arch = "arm""#).unwrap();
+        assert!(expr.eval(&arm()));
+        let expr = CfgExpr::parse(r#"target_arch = "x86_64""#).unwrap();
+        assert!(!expr.eval(&arm()));
+    }
+
+    #[test]
+    fn parses_and_evaluates_bare_identifier() {
+        let expr = CfgExpr::parse("none").unwrap();
+        assert!(expr.eval(&arm()));
+        let expr = CfgExpr::parse("gnu").unwrap();
+        assert!(!expr.eval(&arm()));
+    }
+
+    #[test]
+    fn parses_and_evaluates_all_any_not() {
+        let expr = CfgExpr::parse(r#"all(target_arch = "arm", target_os = "eabi")"#).unwrap();
+        assert!(expr.eval(&arm()));
+
+        let expr = CfgExpr::parse(r#"any(target_arch = "x86_64", target_os = "eabi")"#).unwrap();
+        assert!(expr.eval(&arm()));
+
+        let expr = CfgExpr::parse(r#"not(target_arch = "x86_64")"#).unwrap();
+        assert!(expr.eval(&arm()));
+    }
+
+    #[test]
+    fn rejects_malformed_input() {
+        assert!(CfgExpr::parse("all(target_arch = \"arm\"").is_err());
+        assert!(CfgExpr::parse("not(a, b)").is_err());
+        assert!(CfgExpr::parse(r#"target_arch = "arm" garbage"#).is_err());
+    }
+}
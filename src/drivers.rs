@@ -0,0 +1,234 @@
+//! Compiler-driver detection and system-include probing, covering GCC, Clang,
+//! and the MSVC family (`clang-cl`, `cl.exe`) so the wrapper isn't limited to
+//! arm-gcc cross builds.
+
+use std::path::Path;
+use std::process::{Command, Stdio};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DriverFamily {
+    Gcc,
+    Clang,
+    MsvcCl,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct Driver {
+    pub family: DriverFamily,
+    pub is_cpp: bool,
+}
+
+impl Driver {
+    /// The `-extra-arg` form clang-tidy expects for a system include
+    /// directory under this driver's command-line conventions.
+    pub fn format_include_arg(&self, path: &str) -> String {
+        match self.family {
+            DriverFamily::MsvcCl => format!("/I{}", path),
+            DriverFamily::Gcc | DriverFamily::Clang => format!("-I{}", path),
+        }
+    }
+
+    /// The `-extra-arg` form for a framework search directory (Clang/Darwin only).
+    pub fn format_framework_arg(&self, path: &str) -> String {
+        format!("-iframework{}", path)
+    }
+}
+
+/// Detect the driver family and source language from the compiler's path.
+pub fn detect(compiler_path: &str) -> Driver {
+    let lower = compiler_path.to_ascii_lowercase();
+    let file_name = Path::new(&lower)
+        .file_name()
+        .and_then(|f| f.to_str())
+        .unwrap_or(&lower);
+
+    let family = if file_name.contains("clang-cl") || file_name == "cl" || file_name == "cl.exe" {
+        DriverFamily::MsvcCl
+    } else if file_name.contains("clang") {
+        DriverFamily::Clang
+    } else {
+        DriverFamily::Gcc
+    };
+
+    let is_cpp = file_name.contains("g++") || file_name.contains("c++") || file_name.contains("clang++");
+
+    Driver { family, is_cpp }
+}
+
+/// The system include and (on Darwin, for Clang) framework search paths for a
+/// detected driver.
+pub struct ProbeResult {
+    pub include_dirs: Vec<String>,
+    pub framework_dirs: Vec<String>,
+}
+
+/// Probe a compiler for its system include paths using the strategy that
+/// fits its driver family.
+pub fn probe_includes(
+    compiler_path: &str,
+    driver: Driver,
+    extra_args: &[String],
+) -> anyhow::Result<ProbeResult> {
+    match driver.family {
+        DriverFamily::Gcc | DriverFamily::Clang => {
+            probe_gcc_style(compiler_path, driver, extra_args)
+        }
+        DriverFamily::MsvcCl => Ok(ProbeResult {
+            include_dirs: probe_msvc_include_env(),
+            framework_dirs: Vec::new(),
+        }),
+    }
+}
+
+/// `gcc`/`clang -E -v -` both emit the same `#include <...> search starts
+/// here:` stderr block; Clang additionally emits a `framework directories:`
+/// block on Darwin targets.
+fn probe_gcc_style(
+    compiler_path: &str,
+    driver: Driver,
+    extra_args: &[String],
+) -> anyhow::Result<ProbeResult> {
+    let lang_flag = if driver.is_cpp { "-xc++" } else { "-xc" };
+    let output = Command::new(compiler_path)
+        .args(extra_args)
+        .args([lang_flag, "-E", "-v", "-"])
+        .stdin(Stdio::null())
+        .output()
+        .expect("Failed to run the compiler to extract include paths");
+
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    Ok(parse_search_dirs(&stderr, |p| Path::new(p).exists()))
+}
+
+/// Parse the `#include <...> search starts here:` and (Clang/Darwin-only)
+/// `framework directories:` blocks out of a `-E -v -` stderr dump. `exists`
+/// filters each candidate line down to real paths, so stray diagnostic text
+/// between the markers doesn't get mistaken for a search directory; it's
+/// injected as a parameter so tests can exercise this without touching disk.
+fn parse_search_dirs(stderr: &str, exists: impl Fn(&str) -> bool) -> ProbeResult {
+    let mut include_dirs = Vec::new();
+    let mut framework_dirs = Vec::new();
+    let mut in_includes = false;
+    let mut in_frameworks = false;
+    for line in stderr.lines() {
+        let trimmed = line.trim();
+        if trimmed.starts_with("#include <...> search starts here:") {
+            in_includes = true;
+            in_frameworks = false;
+            continue;
+        }
+        if trimmed.starts_with("framework directories:") {
+            in_frameworks = true;
+            in_includes = false;
+            continue;
+        }
+        if trimmed.starts_with("End of search list.") {
+            in_includes = false;
+            in_frameworks = false;
+            continue;
+        }
+        if in_includes && !trimmed.is_empty() && exists(trimmed) {
+            include_dirs.push(trimmed.replace('\\', "/"));
+        }
+        if in_frameworks && !trimmed.is_empty() && exists(trimmed) {
+            framework_dirs.push(trimmed.replace('\\', "/"));
+        }
+    }
+    ProbeResult {
+        include_dirs,
+        framework_dirs,
+    }
+}
+
+/// MSVC toolchains set up system includes via the `INCLUDE` environment
+/// variable (as `vcvarsall.bat` does); read it rather than spawning
+/// `cl.exe /nologo /E`, since the env var is always available once the
+/// toolchain environment is active and needs no process spawn to parse.
+fn probe_msvc_include_env() -> Vec<String> {
+    std::env::var("INCLUDE")
+        .unwrap_or_default()
+        .split(';')
+        .map(|p| p.trim())
+        .filter(|p| !p.is_empty())
+        .map(|p| p.replace('\\', "/"))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn detects_gcc_family_and_cpp_flag() {
+        let d = detect("/usr/bin/arm-none-eabi-g++");
+        assert_eq!(d.family, DriverFamily::Gcc);
+        assert!(d.is_cpp);
+
+        let d = detect("arm-none-eabi-gcc");
+        assert_eq!(d.family, DriverFamily::Gcc);
+        assert!(!d.is_cpp);
+    }
+
+    #[test]
+    fn detects_clang_family_and_cpp_flag() {
+        let d = detect("/usr/bin/clang++");
+        assert_eq!(d.family, DriverFamily::Clang);
+        assert!(d.is_cpp);
+
+        let d = detect("clang");
+        assert_eq!(d.family, DriverFamily::Clang);
+        assert!(!d.is_cpp);
+    }
+
+    #[test]
+    fn detects_msvc_family_from_clang_cl_and_cl_exe() {
+        let d = detect("C:/tools/clang-cl.exe");
+        assert_eq!(d.family, DriverFamily::MsvcCl);
+        assert!(!d.is_cpp);
+
+        let d = detect("C:/VC/bin/cl.exe");
+        assert_eq!(d.family, DriverFamily::MsvcCl);
+
+        let d = detect("cl");
+        assert_eq!(d.family, DriverFamily::MsvcCl);
+    }
+
+    #[test]
+    fn parses_include_search_dirs() {
+        let stderr = "ignored preamble\n\
+            #include <...> search starts here:\n \
+            /usr/include\n \
+            /usr/local/include\n\
+            End of search list.\n";
+        let result = parse_search_dirs(stderr, |_| true);
+        assert_eq!(result.include_dirs, vec!["/usr/include", "/usr/local/include"]);
+        assert!(result.framework_dirs.is_empty());
+    }
+
+    #[test]
+    fn parses_darwin_framework_directories_block() {
+        let stderr = "#include <...> search starts here:\n \
+            /usr/include\n\
+            End of search list.\n\
+            framework directories:\n \
+            /System/Library/Frameworks\n \
+            /Library/Frameworks\n";
+        let result = parse_search_dirs(stderr, |_| true);
+        assert_eq!(result.include_dirs, vec!["/usr/include"]);
+        assert_eq!(
+            result.framework_dirs,
+            vec!["/System/Library/Frameworks", "/Library/Frameworks"]
+        );
+    }
+
+    #[test]
+    fn filters_candidates_through_exists_and_skips_blank_lines() {
+        let stderr = "#include <...> search starts here:\n \
+            /real\n\
+            \n \
+            /fake\n\
+            End of search list.\n";
+        let result = parse_search_dirs(stderr, |p| p == "/real");
+        assert_eq!(result.include_dirs, vec!["/real"]);
+    }
+}
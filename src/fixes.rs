@@ -0,0 +1,285 @@
+//! Apply `clang-tidy -export-fixes` suggestions to disk, or preview them as a
+//! unified diff instead of writing in place.
+
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::fs;
+
+#[derive(Deserialize, Default)]
+struct ExportedFixes {
+    #[serde(rename = "Diagnostics", default)]
+    diagnostics: Vec<Diagnostic>,
+}
+
+#[derive(Deserialize)]
+struct Diagnostic {
+    #[serde(rename = "DiagnosticMessage")]
+    message: DiagnosticMessage,
+}
+
+#[derive(Deserialize)]
+struct DiagnosticMessage {
+    #[serde(rename = "Replacements", default)]
+    replacements: Vec<Replacement>,
+}
+
+#[derive(Deserialize, Clone)]
+struct Replacement {
+    #[serde(rename = "FilePath")]
+    file_path: String,
+    #[serde(rename = "Offset")]
+    offset: usize,
+    #[serde(rename = "Length")]
+    length: usize,
+    #[serde(rename = "ReplacementText")]
+    replacement_text: String,
+}
+
+/// Parse several `-export-fixes` YAML files and apply their replacements to
+/// disk, or (when `dry_run` is set) print a diff of what would change without
+/// writing. Replacements for the same target file are merged across all of
+/// the given YAMLs *before* any file is read from or written to disk, so
+/// concurrent clang-tidy runs over translation units that share a header
+/// don't clobber each other's offsets: each physical file is read once and
+/// the union of its replacements (possibly emitted by several TUs) is
+/// applied in a single pass.
+pub fn apply_fixes_many(yaml_paths: &[std::path::PathBuf], dry_run: bool) -> anyhow::Result<()> {
+    let mut by_file: HashMap<String, Vec<Replacement>> = HashMap::new();
+    for yaml_path in yaml_paths {
+        let yaml = fs::read_to_string(yaml_path)?;
+        let fixes: ExportedFixes = serde_yaml::from_str(&yaml)?;
+        for diag in fixes.diagnostics {
+            for r in diag.message.replacements {
+                by_file.entry(r.file_path.clone()).or_default().push(r);
+            }
+        }
+    }
+
+    for (file_path, replacements) in by_file {
+        let original = fs::read(&file_path)?;
+        let mut buf = original.clone();
+        let kept = apply_replacements(&file_path, &mut buf, replacements, !dry_run);
+
+        if dry_run {
+            if !kept.is_empty() {
+                println!("--- a/{}", file_path);
+                println!("+++ b/{}", file_path);
+                let mut line_delta: i64 = 0;
+                // Replacements on the same line are combined into a single
+                // hunk so the preview reflects all of that line's edits.
+                let mut i = 0;
+                while i < kept.len() {
+                    let (_, line_end, _) = line_bounds(&original, kept[i].offset);
+                    let mut j = i + 1;
+                    while j < kept.len() && kept[j].offset < line_end {
+                        j += 1;
+                    }
+                    let (hunk, delta) = diff_hunk(&original, &kept[i..j], line_delta);
+                    println!("{}", hunk);
+                    line_delta += delta;
+                    i = j;
+                }
+            }
+        } else {
+            fs::write(&file_path, &buf)?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Sort `replacements` by descending offset and, for each in turn, skip it if
+/// it runs past the end of `buf` or overlaps a replacement already kept,
+/// otherwise splice it into `buf` when `apply` is set. Returns the kept
+/// replacements in ascending offset order (the order a diff reads in).
+fn apply_replacements(
+    file_path: &str,
+    buf: &mut Vec<u8>,
+    mut replacements: Vec<Replacement>,
+    apply: bool,
+) -> Vec<Replacement> {
+    replacements.sort_by_key(|r| std::cmp::Reverse(r.offset));
+
+    let mut kept = Vec::new();
+    let mut next_allowed_end: Option<usize> = None;
+
+    for r in replacements {
+        let end = r.offset + r.length;
+        if end > buf.len() {
+            eprintln!(
+                "x-clang-tidy: skipping out-of-range replacement in {} at offset {}",
+                file_path, r.offset
+            );
+            continue;
+        }
+        if let Some(limit) = next_allowed_end {
+            if end > limit {
+                eprintln!(
+                    "x-clang-tidy: skipping overlapping replacement in {} at offset {}",
+                    file_path, r.offset
+                );
+                continue;
+            }
+        }
+
+        if apply {
+            buf.splice(r.offset..end, r.replacement_text.bytes());
+        }
+        next_allowed_end = Some(r.offset);
+        kept.push(r);
+    }
+
+    kept.reverse();
+    kept
+}
+
+/// The byte range `[start, end)` of the line containing `offset` in
+/// `original`, plus that line's 1-based line number.
+fn line_bounds(original: &[u8], offset: usize) -> (usize, usize, usize) {
+    let line_start = original[..offset]
+        .iter()
+        .rposition(|&b| b == b'\n')
+        .map(|p| p + 1)
+        .unwrap_or(0);
+    let line_end = original[offset..]
+        .iter()
+        .position(|&b| b == b'\n')
+        .map(|p| offset + p)
+        .unwrap_or(original.len());
+    let line_no = original[..offset].iter().filter(|&&b| b == b'\n').count() + 1;
+    (line_start, line_end, line_no)
+}
+
+/// A standard unified-diff hunk (`@@ -start,count +start,count @@`) for a run
+/// of replacements that all fall on the same original line, computed against
+/// the original (pre-edit) file content. `line_delta` is the net line count
+/// change from hunks already emitted for this file, needed to keep the `+`
+/// side's line numbers correct. Returns the hunk text and this hunk's own
+/// line delta.
+fn diff_hunk(original: &[u8], replacements: &[Replacement], line_delta: i64) -> (String, i64) {
+    let (line_start, line_end, line_no) = line_bounds(original, replacements[0].offset);
+    let old_line = String::from_utf8_lossy(&original[line_start..line_end]).to_string();
+
+    // Apply right-to-left so each edit's offset stays valid against the ones
+    // still ahead of it in the line.
+    let mut new_line_bytes = old_line.as_bytes().to_vec();
+    for r in replacements.iter().rev() {
+        let rel_start = r.offset - line_start;
+        let rel_end = (r.offset + r.length).min(line_end) - line_start;
+        new_line_bytes.splice(rel_start..rel_end, r.replacement_text.bytes());
+    }
+    let new_line = String::from_utf8_lossy(&new_line_bytes).to_string();
+
+    let new_count = new_line.split('\n').count();
+    let new_start = line_no as i64 + line_delta;
+
+    let mut hunk = format!("@@ -{},1 +{},{} @@", line_no, new_start, new_count);
+    hunk.push_str(&format!("\n-{}", old_line));
+    for l in new_line.split('\n') {
+        hunk.push_str(&format!("\n+{}", l));
+    }
+
+    (hunk, new_count as i64 - 1)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn replacement(offset: usize, length: usize, text: &str) -> Replacement {
+        Replacement {
+            file_path: "f.c".to_string(),
+            offset,
+            length,
+            replacement_text: text.to_string(),
+        }
+    }
+
+    #[test]
+    fn applies_non_overlapping_replacements_in_one_pass() {
+        let mut buf = b"int a = 1;".to_vec();
+        let replacements = vec![replacement(4, 1, "bb"), replacement(8, 1, "2")];
+        let kept = apply_replacements("f.c", &mut buf, replacements, true);
+        assert_eq!(kept.len(), 2);
+        assert_eq!(buf, b"int bb = 2;");
+    }
+
+    #[test]
+    fn skips_overlapping_replacement() {
+        let mut buf = b"int a = 1;".to_vec();
+        // Both touch byte 4; only the first one encountered in descending
+        // offset order (here, the narrower one at offset 4) should survive.
+        let replacements = vec![replacement(4, 1, "bb"), replacement(3, 2, "cc")];
+        let kept = apply_replacements("f.c", &mut buf, replacements, true);
+        assert_eq!(kept.len(), 1);
+        assert_eq!(kept[0].offset, 4);
+        assert_eq!(buf, b"int bb = 1;");
+    }
+
+    #[test]
+    fn skips_out_of_range_replacement() {
+        let mut buf = b"short".to_vec();
+        let replacements = vec![replacement(10, 2, "xx")];
+        let kept = apply_replacements("f.c", &mut buf, replacements, true);
+        assert!(kept.is_empty());
+        assert_eq!(buf, b"short");
+    }
+
+    #[test]
+    fn dry_run_leaves_buf_untouched_but_reports_kept() {
+        let mut buf = b"int a = 1;".to_vec();
+        let replacements = vec![replacement(4, 1, "bb")];
+        let kept = apply_replacements("f.c", &mut buf, replacements, false);
+        assert_eq!(kept.len(), 1);
+        assert_eq!(buf, b"int a = 1;");
+    }
+
+    #[test]
+    fn diff_hunk_combines_same_line_replacements() {
+        let original = b"int a = 1, b = 2;\n".to_vec();
+        let mut buf = original.clone();
+        let replacements = vec![replacement(4, 1, "aa"), replacement(11, 1, "bb")];
+        let kept = apply_replacements("f.c", &mut buf, replacements, false);
+        assert_eq!(kept.len(), 2);
+        let (hunk, _delta) = diff_hunk(&original, &kept, 0);
+        assert!(hunk.contains("+int aa = 1, bb = 2;"));
+        assert_eq!(hunk.lines().filter(|l| l.starts_with('+')).count(), 1);
+    }
+
+    #[test]
+    fn merges_replacements_for_a_shared_file_across_multiple_yamls() {
+        // Two translation units both emit a fix against the same shared
+        // header, each computed against the pristine file. Applying the two
+        // export-fixes YAMLs independently (read-edit-write per YAML) would
+        // apply the second one's offsets against the already-edited file and
+        // corrupt it; apply_fixes_many must merge both sets of replacements
+        // before touching the file on disk.
+        let dir = std::env::temp_dir().join(format!(
+            "x-clang-tidy-fixes-test-{}-{}",
+            std::process::id(),
+            line!()
+        ));
+        fs::create_dir_all(&dir).unwrap();
+        let header = dir.join("shared.h");
+        fs::write(&header, b"int a = 1, b = 2;\n").unwrap();
+
+        let yaml_for = |offset: usize, length: usize, text: &str| {
+            format!(
+                "Diagnostics:\n  - DiagnosticMessage:\n      Replacements:\n        - FilePath: '{}'\n          Offset: {}\n          Length: {}\n          ReplacementText: '{}'\n",
+                header.display().to_string().replace('\\', "\\\\"),
+                offset,
+                length,
+                text
+            )
+        };
+        let yaml_a = dir.join("tu-a.yaml");
+        let yaml_b = dir.join("tu-b.yaml");
+        fs::write(&yaml_a, yaml_for(4, 1, "aa")).unwrap();
+        fs::write(&yaml_b, yaml_for(11, 1, "bb")).unwrap();
+
+        apply_fixes_many(&[yaml_a, yaml_b], false).unwrap();
+
+        assert_eq!(fs::read(&header).unwrap(), b"int aa = 1, bb = 2;\n");
+        fs::remove_dir_all(&dir).ok();
+    }
+}
@@ -0,0 +1,282 @@
+//! Extract a compiler's system include paths, caching the result on disk
+//! keyed by the compiler binary's mtime so repeated invocations (one per file
+//! in a build) don't each re-shell out to probe the compiler.
+
+use crate::drivers::{self, Driver};
+use serde::{Deserialize, Serialize};
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{Hash, Hasher};
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+#[derive(Serialize, Deserialize)]
+struct CacheEntry {
+    mtime_secs: u64,
+    mtime_nanos: u32,
+    /// Include/framework search paths, pre-formatted as `-extra-arg` flag
+    /// fragments (e.g. `-Ifoo`, `/Ifoo`, `-iframeworkfoo`).
+    flags: Vec<String>,
+}
+
+/// Returns the compiler's system include paths as ready-to-use `-extra-arg`
+/// flag fragments, probing the compiler with a driver-appropriate strategy
+/// and caching the result keyed by the compiler binary's canonical path,
+/// `extra_args`, and the detected driver.
+pub fn extract_compiler_includes(compiler: &str, extra_args: &[String]) -> anyhow::Result<Vec<String>> {
+    let driver = drivers::detect(compiler);
+
+    let canonical = std::fs::canonicalize(compiler).unwrap_or_else(|_| PathBuf::from(compiler));
+    let mtime = std::fs::metadata(&canonical).and_then(|m| m.modified()).ok();
+
+    let cache_path = cache_entry_path(&canonical, extra_args, driver);
+
+    if let (Some(mtime), Some(cache_path)) = (mtime, cache_path.as_ref()) {
+        if let Some(entry) = read_cache_entry(cache_path) {
+            let (secs, nanos) = split_mtime(mtime);
+            let still_fresh = entry.mtime_secs == secs && entry.mtime_nanos == nanos;
+            let dirs_still_exist = entry.flags.iter().all(|f| flag_path_exists(f));
+            if still_fresh && dirs_still_exist {
+                return Ok(entry.flags);
+            }
+        }
+    }
+
+    let probed = drivers::probe_includes(compiler, driver, extra_args)?;
+    let flags: Vec<String> = probed
+        .include_dirs
+        .iter()
+        .map(|p| driver.format_include_arg(p))
+        .chain(probed.framework_dirs.iter().map(|p| driver.format_framework_arg(p)))
+        .collect();
+
+    if let (Some(mtime), Some(cache_path)) = (mtime, cache_path) {
+        let (secs, nanos) = split_mtime(mtime);
+        let entry = CacheEntry {
+            mtime_secs: secs,
+            mtime_nanos: nanos,
+            flags: flags.clone(),
+        };
+        write_cache_entry(&cache_path, &entry);
+    }
+
+    Ok(flags)
+}
+
+/// Strip the `-I`/`/I`/`-iframework` prefix off a formatted flag to recover
+/// the directory it points at, so a stale cache entry pointing at a
+/// since-removed directory can be detected.
+fn flag_path_exists(flag: &str) -> bool {
+    let path = flag
+        .strip_prefix("-iframework")
+        .or_else(|| flag.strip_prefix("-I"))
+        .or_else(|| flag.strip_prefix("/I"))
+        .unwrap_or(flag);
+    Path::new(path).exists()
+}
+
+fn split_mtime(mtime: SystemTime) -> (u64, u32) {
+    match mtime.duration_since(SystemTime::UNIX_EPOCH) {
+        Ok(d) => (d.as_secs(), d.subsec_nanos()),
+        Err(_) => (0, 0),
+    }
+}
+
+fn cache_key(canonical: &Path, extra_args: &[String], driver: Driver) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    canonical.hash(&mut hasher);
+    extra_args.hash(&mut hasher);
+    format!("{:?}", driver).hash(&mut hasher);
+    // MSVC-family includes come from the `INCLUDE` env var rather than the
+    // compiler binary, which the mtime check in `extract_compiler_includes`
+    // can't see change.
+    if driver.family == drivers::DriverFamily::MsvcCl {
+        std::env::var("INCLUDE").unwrap_or_default().hash(&mut hasher);
+    }
+    hasher.finish()
+}
+
+fn cache_entry_path(canonical: &Path, extra_args: &[String], driver: Driver) -> Option<PathBuf> {
+    let dir = cache_dir()?;
+    std::fs::create_dir_all(&dir).ok()?;
+    let key = cache_key(canonical, extra_args, driver);
+    Some(dir.join(format!("{:016x}.json", key)))
+}
+
+fn cache_dir() -> Option<PathBuf> {
+    if let Ok(xdg) = std::env::var("XDG_CACHE_HOME") {
+        if !xdg.is_empty() {
+            return Some(PathBuf::from(xdg).join("x-clang-tidy"));
+        }
+    }
+    if let Ok(home) = std::env::var("HOME") {
+        return Some(PathBuf::from(home).join(".cache").join("x-clang-tidy"));
+    }
+    if let Ok(local_app_data) = std::env::var("LOCALAPPDATA") {
+        return Some(PathBuf::from(local_app_data).join("x-clang-tidy").join("cache"));
+    }
+    None
+}
+
+fn read_cache_entry(path: &Path) -> Option<CacheEntry> {
+    let text = std::fs::read_to_string(path).ok()?;
+    serde_json::from_str(&text).ok()
+}
+
+fn write_cache_entry(path: &Path, entry: &CacheEntry) {
+    if let Ok(text) = serde_json::to_string(entry) {
+        let _ = std::fs::write(path, text);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use once_cell::sync::Lazy;
+    use std::sync::Mutex;
+
+    /// `cache_key`/`extract_compiler_includes` read process-global env vars
+    /// (`INCLUDE`, `XDG_CACHE_HOME`); serialize the tests that touch them so
+    /// they don't stomp on each other when run concurrently.
+    static ENV_LOCK: Lazy<Mutex<()>> = Lazy::new(|| Mutex::new(()));
+
+    fn unique_temp_dir(tag: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!(
+            "x-clang-tidy-include-cache-test-{}-{}-{}",
+            std::process::id(),
+            tag,
+            std::time::SystemTime::now()
+                .duration_since(SystemTime::UNIX_EPOCH)
+                .unwrap()
+                .as_nanos()
+        ));
+        std::fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    #[test]
+    fn cache_key_differs_by_extra_args_and_driver() {
+        let gcc = Driver {
+            family: drivers::DriverFamily::Gcc,
+            is_cpp: false,
+        };
+        let clang = Driver {
+            family: drivers::DriverFamily::Clang,
+            is_cpp: false,
+        };
+        let path = Path::new("/usr/bin/gcc");
+        let base = cache_key(path, &[], gcc);
+        assert_ne!(base, cache_key(path, &["-m32".to_string()], gcc));
+        assert_ne!(base, cache_key(path, &[], clang));
+    }
+
+    #[test]
+    fn cache_key_varies_with_include_env_only_for_msvc() {
+        let _guard = ENV_LOCK.lock().unwrap();
+        let saved = std::env::var("INCLUDE").ok();
+
+        let path = Path::new("/usr/bin/cl.exe");
+        let msvc = Driver {
+            family: drivers::DriverFamily::MsvcCl,
+            is_cpp: false,
+        };
+        let gcc = Driver {
+            family: drivers::DriverFamily::Gcc,
+            is_cpp: false,
+        };
+
+        unsafe { std::env::set_var("INCLUDE", "C:\\a") };
+        let msvc_a = cache_key(path, &[], msvc);
+        let gcc_a = cache_key(path, &[], gcc);
+        unsafe { std::env::set_var("INCLUDE", "C:\\b") };
+        let msvc_b = cache_key(path, &[], msvc);
+        let gcc_b = cache_key(path, &[], gcc);
+
+        assert_ne!(msvc_a, msvc_b);
+        assert_eq!(gcc_a, gcc_b);
+
+        match saved {
+            Some(v) => unsafe { std::env::set_var("INCLUDE", v) },
+            None => unsafe { std::env::remove_var("INCLUDE") },
+        }
+    }
+
+    #[test]
+    fn flag_path_exists_strips_known_prefixes() {
+        let dir = unique_temp_dir("flag-path");
+        let existing = dir.display().to_string();
+        assert!(flag_path_exists(&format!("-I{}", existing)));
+        assert!(flag_path_exists(&format!("/I{}", existing)));
+        assert!(flag_path_exists(&format!("-iframework{}", existing)));
+        assert!(!flag_path_exists(&format!("-I{}/does-not-exist", existing)));
+        std::fs::remove_dir_all(&dir).ok();
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn extract_compiler_includes_hits_cache_then_invalidates_on_mtime_and_missing_dir() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let _guard = ENV_LOCK.lock().unwrap();
+        let saved_xdg = std::env::var("XDG_CACHE_HOME").ok();
+
+        let dir = unique_temp_dir("probe");
+        let cache_home = dir.join("cache");
+        let include_dir = dir.join("include");
+        std::fs::create_dir_all(&cache_home).unwrap();
+        std::fs::create_dir_all(&include_dir).unwrap();
+        unsafe { std::env::set_var("XDG_CACHE_HOME", &cache_home) };
+
+        // A fake `gcc` that reports `include_dir` as its system include path
+        // and counts how many times it was actually invoked, so the test can
+        // tell a cache hit (counter unchanged) from a miss (counter bumped).
+        let counter = dir.join("probe_count");
+        std::fs::write(&counter, "").unwrap();
+        let compiler = dir.join("fake-gcc");
+        std::fs::write(
+            &compiler,
+            format!(
+                "#!/bin/sh\necho x >> '{}'\necho '#include <...> search starts here:' >&2\necho ' {}' >&2\necho 'End of search list.' >&2\n",
+                counter.display(),
+                include_dir.display()
+            ),
+        )
+        .unwrap();
+        std::fs::set_permissions(&compiler, std::fs::Permissions::from_mode(0o755)).unwrap();
+
+        let probe_count = || std::fs::read_to_string(&counter).unwrap().lines().count();
+
+        let flags = extract_compiler_includes(compiler.to_str().unwrap(), &[]).unwrap();
+        assert_eq!(flags, vec![format!("-I{}", include_dir.display())]);
+        assert_eq!(probe_count(), 1);
+
+        // Same mtime, same include dir: cache hit, no re-probe.
+        let flags_again = extract_compiler_includes(compiler.to_str().unwrap(), &[]).unwrap();
+        assert_eq!(flags_again, flags);
+        assert_eq!(probe_count(), 1);
+
+        // Touch the compiler's mtime forward: cache entry is now stale.
+        let newer = SystemTime::now() + std::time::Duration::from_secs(2);
+        std::fs::File::options()
+            .write(true)
+            .open(&compiler)
+            .unwrap()
+            .set_modified(newer)
+            .unwrap();
+        extract_compiler_includes(compiler.to_str().unwrap(), &[]).unwrap();
+        assert_eq!(probe_count(), 2);
+
+        // Cache is fresh again at the new mtime, but the cached include dir
+        // has since been removed: that alone must force a re-probe.
+        extract_compiler_includes(compiler.to_str().unwrap(), &[]).unwrap();
+        assert_eq!(probe_count(), 2);
+        std::fs::remove_dir_all(&include_dir).unwrap();
+        extract_compiler_includes(compiler.to_str().unwrap(), &[]).unwrap();
+        assert_eq!(probe_count(), 3);
+
+        match saved_xdg {
+            Some(v) => unsafe { std::env::set_var("XDG_CACHE_HOME", v) },
+            None => unsafe { std::env::remove_var("XDG_CACHE_HOME") },
+        }
+        std::fs::remove_dir_all(&dir).ok();
+    }
+}
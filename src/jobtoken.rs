@@ -0,0 +1,352 @@
+//! A pool of concurrency tokens for bounding how many `clang-tidy` child
+//! processes run at once. Source tokens from an inherited GNU Make jobserver
+//! when present (parsing `--jobserver-auth=` out of `MAKEFLAGS`), otherwise
+//! fall back to an in-process pool sized to the available parallelism.
+
+use std::sync::{Condvar, Mutex};
+
+/// A pool that children acquire a token from before spawning, and return to
+/// when they exit.
+pub enum JobTokenPool {
+    Jobserver(Jobserver),
+    InProcess(InProcessPool),
+}
+
+impl JobTokenPool {
+    /// Build a pool from the inherited jobserver, falling back to
+    /// `std::thread::available_parallelism()` when none is present.
+    pub fn from_env() -> Self {
+        match Jobserver::from_env() {
+            Some(jobserver) => JobTokenPool::Jobserver(jobserver),
+            None => JobTokenPool::InProcess(InProcessPool::new(
+                std::thread::available_parallelism()
+                    .map(|n| n.get())
+                    .unwrap_or(1),
+            )),
+        }
+    }
+
+    /// Block until a token is available. Drop the returned guard to release it.
+    pub fn acquire(&self) -> JobToken<'_> {
+        match self {
+            JobTokenPool::Jobserver(j) => JobToken::Jobserver(j.acquire()),
+            JobTokenPool::InProcess(p) => JobToken::InProcess(p.acquire()),
+        }
+    }
+
+    /// A sane number of long-lived worker threads to fan work out across:
+    /// the pool's exact capacity when it's in-process, or
+    /// `available_parallelism()` when sourced from an inherited jobserver
+    /// (whose token count isn't queryable, but whose `acquire()` calls
+    /// throttle actual child-process concurrency regardless of how many
+    /// worker threads are blocked on it).
+    pub fn worker_threads(&self) -> usize {
+        match self {
+            JobTokenPool::Jobserver(_) => std::thread::available_parallelism()
+                .map(|n| n.get())
+                .unwrap_or(1),
+            JobTokenPool::InProcess(p) => p.capacity,
+        }
+    }
+}
+
+/// Held for as long as a child process is running; dropping it releases the
+/// token back to whichever pool issued it.
+#[allow(dead_code)]
+pub enum JobToken<'a> {
+    Jobserver(JobserverToken<'a>),
+    InProcess(InProcessToken<'a>),
+}
+
+/// An in-process counting semaphore, used when no GNU Make jobserver was
+/// inherited.
+pub struct InProcessPool {
+    capacity: usize,
+    available: Mutex<usize>,
+    available_cond: Condvar,
+}
+
+impl InProcessPool {
+    pub fn new(capacity: usize) -> Self {
+        let capacity = capacity.max(1);
+        InProcessPool {
+            capacity,
+            available: Mutex::new(capacity),
+            available_cond: Condvar::new(),
+        }
+    }
+
+    fn acquire(&self) -> InProcessToken<'_> {
+        let mut available = self.available.lock().unwrap();
+        while *available == 0 {
+            available = self.available_cond.wait(available).unwrap();
+        }
+        *available -= 1;
+        InProcessToken { pool: self }
+    }
+
+    fn release(&self) {
+        *self.available.lock().unwrap() += 1;
+        self.available_cond.notify_one();
+    }
+}
+
+pub struct InProcessToken<'a> {
+    pool: &'a InProcessPool,
+}
+
+impl Drop for InProcessToken<'_> {
+    fn drop(&mut self) {
+        self.pool.release();
+    }
+}
+
+/// A client for an inherited GNU Make jobserver.
+pub struct Jobserver {
+    #[cfg(unix)]
+    inner: unix::Client,
+    #[cfg(windows)]
+    inner: windows::Client,
+}
+
+impl Jobserver {
+    /// Parse `MAKEFLAGS` for `--jobserver-auth=` (or the legacy
+    /// `--jobserver-fds=`) and connect to the inherited jobserver, if any.
+    pub fn from_env() -> Option<Self> {
+        let makeflags = std::env::var("MAKEFLAGS").ok()?;
+
+        #[cfg(unix)]
+        {
+            unix::Client::from_makeflags(&makeflags).map(|inner| Jobserver { inner })
+        }
+        #[cfg(windows)]
+        {
+            windows::Client::from_makeflags(&makeflags).map(|inner| Jobserver { inner })
+        }
+        #[cfg(not(any(unix, windows)))]
+        {
+            None
+        }
+    }
+
+    fn acquire(&self) -> JobserverToken<'_> {
+        JobserverToken {
+            inner: self.inner.acquire(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn falls_back_to_in_process_pool_without_makeflags() {
+        // SAFETY: no other thread in this test binary touches MAKEFLAGS.
+        let saved = std::env::var("MAKEFLAGS").ok();
+        unsafe { std::env::remove_var("MAKEFLAGS") };
+        let pool = JobTokenPool::from_env();
+        assert!(matches!(pool, JobTokenPool::InProcess(_)));
+        if let Some(saved) = saved {
+            unsafe { std::env::set_var("MAKEFLAGS", saved) };
+        }
+    }
+}
+
+/// Dropping this releases the token back to the jobserver pipe/semaphore.
+#[allow(dead_code)]
+pub struct JobserverToken<'a> {
+    #[cfg(unix)]
+    inner: unix::ClientToken<'a>,
+    #[cfg(windows)]
+    inner: windows::ClientToken<'a>,
+    #[cfg(not(any(unix, windows)))]
+    inner: std::marker::PhantomData<&'a ()>,
+}
+
+#[cfg(unix)]
+mod unix {
+    use std::fs::File;
+    use std::io::{Read, Write};
+    use std::os::unix::io::{FromRawFd, RawFd};
+
+    /// Read/write ends of the jobserver's pipe, as inherited from `make`.
+    pub struct Client {
+        read: RawFd,
+        write: RawFd,
+    }
+
+    impl Client {
+        pub fn from_makeflags(makeflags: &str) -> Option<Self> {
+            for part in makeflags.split_whitespace() {
+                let Some(auth) = part
+                    .strip_prefix("--jobserver-auth=")
+                    .or_else(|| part.strip_prefix("--jobserver-fds="))
+                else {
+                    continue;
+                };
+                let mut fds = auth.splitn(2, ',');
+                let read = fds.next().and_then(|s| s.parse().ok());
+                let write = fds.next().and_then(|s| s.parse().ok());
+                let (Some(read), Some(write)) = (read, write) else {
+                    continue;
+                };
+                return Some(Client { read, write });
+            }
+            None
+        }
+
+        pub fn acquire(&self) -> ClientToken<'_> {
+            // SAFETY: `read` is a fd inherited from the parent `make` process for
+            // the lifetime of this process; we never close it.
+            let mut file = unsafe { File::from_raw_fd(self.read) };
+            let mut byte = [0u8; 1];
+            file.read_exact(&mut byte).expect("failed to read jobserver token");
+            std::mem::forget(file);
+            ClientToken {
+                client: self,
+                byte: byte[0],
+            }
+        }
+
+        fn release(&self, byte: u8) {
+            // SAFETY: see `acquire`.
+            let mut file = unsafe { File::from_raw_fd(self.write) };
+            let _ = file.write_all(&[byte]);
+            std::mem::forget(file);
+        }
+    }
+
+    pub struct ClientToken<'a> {
+        client: &'a Client,
+        byte: u8,
+    }
+
+    impl Drop for ClientToken<'_> {
+        fn drop(&mut self) {
+            self.client.release(self.byte);
+        }
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+
+        #[test]
+        fn parses_jobserver_auth_past_leading_tokens() {
+            let client = Client::from_makeflags("-j -w --jobserver-auth=3,4").unwrap();
+            assert_eq!(client.read, 3);
+            assert_eq!(client.write, 4);
+        }
+
+        #[test]
+        fn parses_legacy_jobserver_fds_spelling() {
+            let client = Client::from_makeflags("--jobserver-fds=5,6").unwrap();
+            assert_eq!(client.read, 5);
+            assert_eq!(client.write, 6);
+        }
+
+        #[test]
+        fn rejects_malformed_fd_pair_without_panicking() {
+            assert!(Client::from_makeflags("--jobserver-auth=3").is_none());
+            assert!(Client::from_makeflags("--jobserver-auth=x,y").is_none());
+            assert!(Client::from_makeflags("-j -w").is_none());
+        }
+
+        #[test]
+        fn empty_makeflags_yields_none() {
+            assert!(Client::from_makeflags("").is_none());
+        }
+    }
+}
+
+#[cfg(windows)]
+mod windows {
+    use std::ffi::c_void;
+    use std::os::windows::ffi::OsStrExt;
+
+    type Handle = *mut c_void;
+
+    const SEMAPHORE_ALL_ACCESS: u32 = 0x1F0003;
+    const INFINITE: u32 = 0xFFFF_FFFF;
+
+    #[link(name = "kernel32")]
+    extern "system" {
+        fn OpenSemaphoreW(desired_access: u32, inherit_handle: i32, name: *const u16) -> Handle;
+        fn WaitForSingleObject(handle: Handle, millis: u32) -> u32;
+        fn ReleaseSemaphore(handle: Handle, release_count: i32, prev_count: *mut i32) -> i32;
+        fn CloseHandle(handle: Handle) -> i32;
+    }
+
+    /// A handle to the named semaphore `make` created for this jobserver.
+    pub struct Client {
+        handle: Handle,
+    }
+
+    unsafe impl Send for Client {}
+    unsafe impl Sync for Client {}
+
+    impl Client {
+        pub fn from_makeflags(makeflags: &str) -> Option<Self> {
+            for part in makeflags.split_whitespace() {
+                let Some(name) = part.strip_prefix("--jobserver-auth=") else {
+                    continue;
+                };
+                // The pipe form (`R,W`) is not usable on Windows.
+                if name.contains(',') {
+                    continue;
+                }
+                let wide: Vec<u16> = std::ffi::OsStr::new(name)
+                    .encode_wide()
+                    .chain(std::iter::once(0))
+                    .collect();
+                let handle = unsafe { OpenSemaphoreW(SEMAPHORE_ALL_ACCESS, 0, wide.as_ptr()) };
+                if handle.is_null() {
+                    return None;
+                }
+                return Some(Client { handle });
+            }
+            None
+        }
+
+        pub fn acquire(&self) -> ClientToken<'_> {
+            unsafe { WaitForSingleObject(self.handle, INFINITE) };
+            ClientToken { client: self }
+        }
+
+        fn release(&self) {
+            let mut prev = 0i32;
+            unsafe { ReleaseSemaphore(self.handle, 1, &mut prev) };
+        }
+    }
+
+    impl Drop for Client {
+        fn drop(&mut self) {
+            unsafe { CloseHandle(self.handle) };
+        }
+    }
+
+    pub struct ClientToken<'a> {
+        client: &'a Client,
+    }
+
+    impl Drop for ClientToken<'_> {
+        fn drop(&mut self) {
+            self.client.release();
+        }
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+
+        #[test]
+        fn rejects_malformed_auth_without_panicking() {
+            // The pipe form isn't usable on Windows and should be skipped,
+            // and a scan with no `--jobserver-auth=` at all should return
+            // `None` rather than panicking.
+            assert!(Client::from_makeflags("-j -w --jobserver-auth=3,4").is_none());
+            assert!(Client::from_makeflags("-j -w").is_none());
+        }
+    }
+}
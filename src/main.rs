@@ -1,3 +1,12 @@
+mod cfgexpr;
+mod drivers;
+mod fixes;
+mod include_cache;
+mod jobtoken;
+mod runner;
+
+use cfgexpr::{CfgExpr, TargetCfg};
+
 use handlebars::{Handlebars, handlebars_helper};
 use once_cell::sync::OnceCell;
 use serde::Deserialize;
@@ -14,6 +23,21 @@ struct Config {
     extra_args: Vec<String>,
     #[serde(rename = "filter-args")]
     filter_args: Option<Vec<String>>,
+    #[serde(rename = "apply-fixes", default)]
+    apply_fixes: bool,
+    #[serde(rename = "overlays", default)]
+    overlays: Vec<ConfigOverlay>,
+}
+
+/// A conditional overlay merged into the base `extra-args`/`filter-args`
+/// lists when `when` evaluates true against the parsed `--target=` triple.
+#[derive(Deserialize)]
+struct ConfigOverlay {
+    when: String,
+    #[serde(rename = "extra-args", default)]
+    extra_args: Vec<String>,
+    #[serde(rename = "filter-args", default)]
+    filter_args: Vec<String>,
 }
 
 pub fn cwd() -> &'static std::path::PathBuf {
@@ -62,6 +86,13 @@ fn is_valid_config_path<T: AsRef<str>>(path: T) -> bool {
     std::path::Path::new(path).exists() && (path.ends_with(".json") || path.ends_with(".json.hbt"))
 }
 
+/// Arguments that `x-clang-tidy` itself consumes and that must not be
+/// forwarded to `clang-tidy`.
+fn is_meta_flag<T: AsRef<str>>(arg: T) -> bool {
+    let arg = arg.as_ref();
+    is_valid_config_path(arg) || arg == "--apply-fixes" || arg == "--dry-run"
+}
+
 // a helper that return env variables
 handlebars_helper!(hb_env: |name: String| {
     match std::env::var(&name) {
@@ -107,6 +138,10 @@ fn _main() -> anyhow::Result<()> {
 
     let mut compiler_extra_args: Vec<String> = Vec::new();
     // find --target= argument
+    let target_triple = extra_args
+        .iter()
+        .find(|arg| arg.starts_with("--target="))
+        .map(|arg| arg["--target=".len()..].to_string());
     if let Some(target_arg) = extra_args.iter().find(|arg| arg.starts_with("--target=")) {
         compiler_extra_args.push(target_arg.clone());
     }
@@ -116,13 +151,46 @@ fn _main() -> anyhow::Result<()> {
     }
 
     // Get GCC system include paths
-    let include_paths = extract_compiler_includes(gcc_path, &compiler_extra_args)?;
+    let include_flags = include_cache::extract_compiler_includes(gcc_path, &compiler_extra_args)?;
 
-    let clang_tidy_args = match config.filter_args {
+    let dry_run = extra_args.iter().any(|arg| arg == "--dry-run");
+    let apply_fixes_enabled =
+        config.apply_fixes || dry_run || extra_args.iter().any(|arg| arg == "--apply-fixes");
+
+    // Merge in any overlays whose cfg-expression matches the --target= triple,
+    // so one checked-in config can serve multiple cross targets.
+    let mut effective_extra_args = config.extra_args.clone();
+    let mut effective_filter_args = config.filter_args.clone();
+    if let Some(triple) = &target_triple {
+        let target_cfg = TargetCfg::from_triple(triple);
+        for overlay in &config.overlays {
+            let matches = match CfgExpr::parse(&overlay.when) {
+                Ok(expr) => expr.eval(&target_cfg),
+                Err(e) => {
+                    eprintln!(
+                        "x-clang-tidy: ignoring overlay with invalid `when` expression `{}`: {}",
+                        overlay.when, e
+                    );
+                    false
+                }
+            };
+            if !matches {
+                continue;
+            }
+            effective_extra_args.extend(overlay.extra_args.iter().cloned());
+            if !overlay.filter_args.is_empty() {
+                effective_filter_args
+                    .get_or_insert_with(Vec::new)
+                    .extend(overlay.filter_args.iter().cloned());
+            }
+        }
+    }
+
+    let clang_tidy_args = match effective_filter_args {
         Some(filter_args) => {
             let ea = extra_args
                 .into_iter()
-                .filter(|arg| !is_valid_config_path(arg))
+                .filter(|arg| !is_meta_flag(arg))
                 .collect::<Vec<_>>();
 
             // Create a set of args to filter out, handling both individual args and space-separated options
@@ -145,7 +213,7 @@ fn _main() -> anyhow::Result<()> {
         }
         None => extra_args
             .into_iter()
-            .filter(|arg| !is_valid_config_path(arg))
+            .filter(|arg| !is_meta_flag(arg))
             .collect::<Vec<_>>(),
     };
 
@@ -153,17 +221,66 @@ fn _main() -> anyhow::Result<()> {
     eprintln!("clang_tidy_args: {:?}", clang_tidy_args);
     eprintln!("conf_additional_path: {:?}", conf_additional_path);
 
+    let (leading_args, source_files, trailing_args) = split_source_files(&clang_tidy_args);
+
+    if source_files.len() > 1 {
+        // One export-fixes temp file per translation unit, so concurrent
+        // children don't clobber each other's output.
+        let export_fixes_paths: std::collections::HashMap<String, std::path::PathBuf> =
+            if apply_fixes_enabled {
+                source_files
+                    .iter()
+                    .enumerate()
+                    .map(|(i, file)| (file.clone(), export_fixes_path(i)))
+                    .collect()
+            } else {
+                std::collections::HashMap::new()
+            };
+
+        // Fan out across concurrent clang-tidy processes, one per translation unit.
+        let build_cmd = |file: &str| {
+            let mut cmd = Command::new(&config.clang_tidy);
+            for arg in &effective_extra_args {
+                cmd.arg(format!("-extra-arg={}", arg));
+            }
+            for flag in &include_flags {
+                cmd.arg(format!("-extra-arg={}", flag));
+            }
+            for arg in &leading_args {
+                cmd.arg(arg);
+            }
+            cmd.arg(file);
+            for arg in &trailing_args {
+                cmd.arg(arg);
+            }
+            if let Some(p) = export_fixes_paths.get(file) {
+                cmd.arg(format!("-export-fixes={}", p.display()));
+            }
+            cmd
+        };
+        let all_ok = runner::run_parallel(build_cmd, &source_files)?;
+
+        apply_exported_fixes_many(export_fixes_paths.values().cloned().collect(), dry_run);
+
+        std::process::exit(if all_ok { 0 } else { 1 });
+    }
+
+    let export_fixes_path = apply_fixes_enabled.then(|| export_fixes_path(0));
+
     // Build clang-tidy command
     let mut cmd = Command::new(&config.clang_tidy);
-    for arg in &config.extra_args {
+    for arg in &effective_extra_args {
         cmd.arg(format!("-extra-arg={}", arg));
     }
-    for path in include_paths {
-        cmd.arg(format!("-extra-arg=-I{}", path));
+    for flag in include_flags {
+        cmd.arg(format!("-extra-arg={}", flag));
     }
     for arg in &clang_tidy_args {
         cmd.arg(arg);
     }
+    if let Some(p) = &export_fixes_path {
+        cmd.arg(format!("-export-fixes={}", p.display()));
+    }
 
     // Run clang-tidy
     let status = cmd
@@ -174,46 +291,58 @@ fn _main() -> anyhow::Result<()> {
         .map_err(|e| anyhow::anyhow!("Can't launch `{}`: {}", &config.clang_tidy, e))
         .expect("Failed to execute clang-tidy");
 
+    if let Some(p) = &export_fixes_path {
+        apply_exported_fixes(p, dry_run);
+    }
+
     std::process::exit(status.code().unwrap_or(1));
 }
 
-fn is_cpp_compiler(compiler_path: &str) -> bool {
-    let compiler_lower = compiler_path.to_ascii_lowercase();
-    compiler_lower.contains("g++") || compiler_lower.contains("c++")
+/// A unique temp path for a `-export-fixes` YAML file, scoped to this process
+/// and the index of the translation unit it belongs to.
+fn export_fixes_path(index: usize) -> std::path::PathBuf {
+    std::env::temp_dir().join(format!(
+        "x-clang-tidy-fixes-{}-{}.yaml",
+        std::process::id(),
+        index
+    ))
 }
 
-fn extract_compiler_includes(gcc: &str, extra_args: &[String]) -> anyhow::Result<Vec<String>> {
-    let is_cpp = is_cpp_compiler(gcc);
-    let lang_flag = if is_cpp { "-xc++" } else { "-xc" };
-    // Run gcc -xc -E -v -
-    let output = Command::new(gcc)
-        .args(extra_args)
-        .args([lang_flag, "-E", "-v", "-"])
-        .stdin(Stdio::null())
-        .output()
-        .expect("Failed to run gcc to extract include paths");
-
-    let stderr = String::from_utf8_lossy(&output.stderr);
-    let mut includes = Vec::new();
-    let mut in_block = false;
-    for line in stderr.lines() {
-        if line
-            .trim()
-            .starts_with("#include <...> search starts here:")
-        {
-            in_block = true;
-            continue;
-        }
-        if line.trim().starts_with("End of search list.") {
-            break;
-        }
-        if in_block {
-            let path = line.trim();
-            // Only add if path exists and isn't empty
-            if !path.is_empty() && std::path::Path::new(path).exists() {
-                includes.push(path.replace("\\", "/")); // Normalize path
-            }
+fn apply_exported_fixes(path: &std::path::Path, dry_run: bool) {
+    apply_exported_fixes_many(vec![path.to_path_buf()], dry_run);
+}
+
+/// Apply the combined replacements from several per-translation-unit
+/// `-export-fixes` YAML files in one pass. Fixes from a shared header
+/// emitted by more than one translation unit are merged and applied against
+/// the pristine file before anything is written, so one TU's edits can't
+/// shift the offsets another TU's replacements were computed against.
+fn apply_exported_fixes_many(paths: Vec<std::path::PathBuf>, dry_run: bool) {
+    let existing: Vec<_> = paths.into_iter().filter(|p| p.exists()).collect();
+    if let Err(e) = fixes::apply_fixes_many(&existing, dry_run) {
+        eprintln!("x-clang-tidy: failed to apply fixes: {}", e);
+    }
+    for p in &existing {
+        let _ = std::fs::remove_file(p);
+    }
+}
+
+/// Split clang-tidy's positional arguments into the flags before the source
+/// files, the source files themselves, and everything from a literal `--`
+/// onward (the trailing compiler args). Flags are anything starting with `-`;
+/// everything else before `--` is treated as a source file.
+fn split_source_files(args: &[String]) -> (Vec<String>, Vec<String>, Vec<String>) {
+    let split_at = args.iter().position(|a| a == "--").unwrap_or(args.len());
+    let (before, after) = args.split_at(split_at);
+
+    let mut leading = Vec::new();
+    let mut files = Vec::new();
+    for arg in before {
+        if arg.starts_with('-') {
+            leading.push(arg.clone());
+        } else {
+            files.push(arg.clone());
         }
     }
-    Ok(includes)
+    (leading, files, after.to_vec())
 }
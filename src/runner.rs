@@ -0,0 +1,69 @@
+//! Fan `clang-tidy` out across concurrent child processes, one per
+//! translation unit, bounded by a [`crate::jobtoken::JobTokenPool`].
+
+use crate::jobtoken::JobTokenPool;
+use std::io::Write;
+use std::process::Command;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::mpsc;
+
+/// Run `build_cmd(file)` once per entry in `files`, gated by a job token.
+/// Work is pulled from a shared index by a fixed-size pool of long-lived
+/// worker threads (sized to [`JobTokenPool::worker_threads`]) rather than one
+/// thread per file, so a large file list can't blow past the OS thread limit.
+/// Each child's stdout/stderr is buffered and flushed whole on completion, so
+/// output from concurrent children never interleaves. Returns `true` if every
+/// child exited successfully.
+pub fn run_parallel(
+    build_cmd: impl Fn(&str) -> Command + Sync,
+    files: &[String],
+) -> anyhow::Result<bool> {
+    let pool = JobTokenPool::from_env();
+    let worker_count = pool.worker_threads().min(files.len()).max(1);
+    let next_file = AtomicUsize::new(0);
+    let (tx, rx) = mpsc::channel();
+
+    std::thread::scope(|scope| {
+        for _ in 0..worker_count {
+            let tx = tx.clone();
+            let pool = &pool;
+            let build_cmd = &build_cmd;
+            let next_file = &next_file;
+            scope.spawn(move || {
+                loop {
+                    let i = next_file.fetch_add(1, Ordering::Relaxed);
+                    let Some(file) = files.get(i) else {
+                        break;
+                    };
+                    let _token = pool.acquire();
+                    let mut cmd = build_cmd(file);
+                    let result = cmd
+                        .stdin(std::process::Stdio::null())
+                        .stdout(std::process::Stdio::piped())
+                        .stderr(std::process::Stdio::piped())
+                        .output();
+                    let _ = tx.send((file.clone(), result));
+                }
+            });
+        }
+        drop(tx);
+
+        let mut all_ok = true;
+        for (file, result) in rx {
+            match result {
+                Ok(output) => {
+                    std::io::stdout().write_all(&output.stdout).ok();
+                    std::io::stderr().write_all(&output.stderr).ok();
+                    if !output.status.success() {
+                        all_ok = false;
+                    }
+                }
+                Err(e) => {
+                    eprintln!("x-clang-tidy: failed to launch clang-tidy for {}: {}", file, e);
+                    all_ok = false;
+                }
+            }
+        }
+        Ok(all_ok)
+    })
+}